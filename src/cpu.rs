@@ -1,3 +1,35 @@
+use std::fs;
+use std::io;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// CHIP-8 programs are loaded starting at 0x200, the space below that being
+// reserved for the interpreter (here, the fontset).
+const PROGRAM_START: usize = 0x200;
+
+// Several opcodes have more than one historically-correct interpretation, and
+// ROMs are written against either the original COSMAC VIP behavior or the
+// later SUPER-CHIP/CHIP-48 behavior. These flags let the host pick which
+// semantics this Cpu runs with; the default matches the COSMAC VIP.
+pub struct Quirks {
+    pub shift_uses_vy: bool,
+    pub bnnn_uses_vx: bool,
+    pub fx55_increments_i: bool,
+    pub reset_vf_on_logic: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            bnnn_uses_vx: false,
+            fx55_increments_i: true,
+            reset_vf_on_logic: true,
+        }
+    }
+}
+
 // The Cpu struct represents the state of the cpu for the chip-8 emulation including
 // memory, registers, and graphics
 pub struct Cpu {
@@ -10,6 +42,7 @@ pub struct Cpu {
     program_counter: u16,
     
     graphics: [u8; 64 * 32],
+    draw_flag: bool,
 
     delay_timer: u8,
     sound_timer: u8,
@@ -18,28 +51,160 @@ pub struct Cpu {
     stack_pointer: u16,
 
     key: [u8; 16],
+
+    rng: StdRng,
+    quirks: Quirks,
+}
+
+impl Default for Cpu {
+    fn default() -> Cpu {
+        Cpu::new()
+    }
 }
 
 impl Cpu {
     pub fn new() -> Cpu {
-        let mut cpu = Cpu { opcode: 0, memory: [0; 4096], register: [0; 16], address_register: 0, 
-                            program_counter: 0x200, graphics: [0; 64 * 32], delay_timer: 0, 
-                            sound_timer: 0, stack: [0; 16], stack_pointer: 0, key: [0; 16] };
+        Cpu::with_seed(rand::random())
+    }
+
+    // Construct a Cpu whose RNG is seeded deterministically, so ROMs that rely
+    // on CXNN produce reproducible traces in tests.
+    pub fn with_seed(seed: u64) -> Cpu {
+        Cpu::build(seed, Quirks::default())
+    }
+
+    // Construct a Cpu with a non-default compatibility mode, e.g. to run
+    // SUPER-CHIP-era ROMs that expect shift/jump/memory opcodes to behave
+    // differently than the original COSMAC VIP.
+    pub fn with_quirks(quirks: Quirks) -> Cpu {
+        Cpu::build(rand::random(), quirks)
+    }
+
+    fn build(seed: u64, quirks: Quirks) -> Cpu {
+        let mut cpu = Cpu { opcode: 0, memory: [0; 4096], register: [0; 16], address_register: 0,
+                            program_counter: 0x200, graphics: [0; 64 * 32], draw_flag: false, delay_timer: 0,
+                            sound_timer: 0, stack: [0; 16], stack_pointer: 0, key: [0; 16],
+                            rng: StdRng::seed_from_u64(seed), quirks };
 
         // allocate the first portion of memory to the fontset
-        for i in 0..80 {
-            cpu.memory[i] = FONTSET[i];
+        for (i, &byte) in FONTSET.iter().enumerate() {
+            cpu.memory[i] = byte;
         }
 
         cpu
     }
 
+    // Read a ROM file from disk and copy it into memory at the program origin.
+    pub fn load_rom(&mut self, path: &str) -> io::Result<()> {
+        let rom = fs::read(path)?;
+
+        if rom.len() > self.memory.len() - PROGRAM_START {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "rom is too large to fit in memory"));
+        }
+
+        self.load_bytes(&rom);
+
+        Ok(())
+    }
+
+    // Copy a ROM already in memory (embedded, downloaded, etc.) into memory at
+    // the program origin.
+    pub fn load_bytes(&mut self, rom: &[u8]) {
+        for (i, &byte) in rom.iter().enumerate() {
+            self.memory[PROGRAM_START + i] = byte;
+        }
+    }
+
+    // Whether the graphics buffer has changed since the last `clear_draw_flag`
+    // call. A host render loop should poll this after each `cycle()` and only
+    // repaint when it's set.
+    pub fn should_draw(&self) -> bool {
+        self.draw_flag
+    }
+
+    pub fn clear_draw_flag(&mut self) {
+        self.draw_flag = false;
+    }
+
+    pub fn framebuffer(&self) -> &[u8; 64 * 32] {
+        &self.graphics
+    }
+
+    // Map a host keypad event onto the 16-key hex keypad.
+    pub fn set_key(&mut self, index: usize, pressed: bool) {
+        self.key[index] = pressed as u8;
+    }
+
+    pub fn clear_keys(&mut self) {
+        for i in 0..self.key.len() {
+            self.key[i] = 0;
+        }
+    }
+
     pub fn cycle(&mut self) {
-        
+
         self.fetch_opcode();
 
+        let opcode = self.opcode;
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let n = (opcode & 0x000F) as u8;
+        let nn = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
+
         // execute
-        // update timers
+        match opcode & 0xF000 {
+            0x0000 => match opcode & 0x00FF {
+                0x00E0 => self.clear_screen(),
+                0x00EE => self.cpu_return(),
+                _ => {
+                    println!("Unknown opcode: {:#06X}", opcode);
+                    self.program_counter += 2;
+                }
+            },
+            0x1000 => self.jump(nnn),
+            0x2000 => self.call(nnn),
+            0x3000 => self.skip_equal(x, nn),
+            0x4000 => self.skip_not_equal(x, nn),
+            0x5000 => self.skip_regs_equal(x, y),
+            0x6000 => self.set_vx_num(x, nn),
+            0x7000 => self.add_vx_num(x, nn),
+            0x8000 => self.opcode_8(x, y, n),
+            0x9000 => self.skip_regs_not_equal(x, y),
+            0xA000 => self.set_adr_reg(nnn),
+            0xB000 => self.jump_add(x, nnn),
+            0xC000 => self.rand_vx(x, nn),
+            0xD000 => self.draw_sprite(x, y, n),
+            0xE000 => match opcode & 0x00FF {
+                0x009E => self.skip_key_press(x, true),
+                0x00A1 => self.skip_key_press(x, false),
+                _ => {
+                    println!("Unknown opcode: {:#06X}", opcode);
+                    self.program_counter += 2;
+                }
+            },
+            0xF000 => self.opcode_f(x, nn),
+            _ => println!("Unknown opcode: {:#06X}", opcode),
+        }
+    }
+
+    // Decrement the delay and sound timers by one. CHIP-8 timers count down at
+    // a fixed 60 Hz regardless of how fast the host drives cycle(), so this
+    // should be called on its own 60 Hz clock rather than once per cycle.
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+    }
+
+    // Whether the sound timer is active; a frontend should play a beep for as
+    // long as this is true.
+    pub fn is_beeping(&self) -> bool {
+        self.sound_timer > 0
     }
 
     fn fetch_opcode(&mut self) {
@@ -47,6 +212,7 @@ impl Cpu {
             (self.memory[(self.program_counter + 1) as usize]) as u16
     }
 
+    #[allow(dead_code)]
     fn nop() {
         // mainly for testing purposes
     }
@@ -56,6 +222,8 @@ impl Cpu {
             self.graphics[i] = 0;
         }
 
+        self.draw_flag = true;
+
         self.program_counter += 2;
     }
 
@@ -67,36 +235,30 @@ impl Cpu {
     }
 
     // 0x1NNN
-    fn jump(&mut self) {
-        self.program_counter = self.opcode & 0x0FFF;
+    fn jump(&mut self, nnn: u16) {
+        self.program_counter = nnn;
     }
 
     // 0x2NNN
-    fn call(&mut self) {
+    fn call(&mut self, nnn: u16) {
         self.stack[self.stack_pointer as usize] = self.program_counter;
         self.stack_pointer += 1;
 
-        self.program_counter = self.opcode & 0x0FFF;
+        self.program_counter = nnn;
     }
 
     // 3XNN: Skip next instruction if VX == NN
-    fn skip_equal(&mut self) {
-        let x = self.opcode & 0x0F00;
-        let val = self.opcode & 0x00FF;
-
-        if self.register[x as usize] == val as u8 {
+    fn skip_equal(&mut self, x: usize, nn: u8) {
+        if self.register[x] == nn {
             self.program_counter += 2;
         }
 
         self.program_counter += 2;
     }
-    
-    // 4XNN: Skip next instruction if VX != NN
-    fn skip_not_equal(&mut self) {
-        let x = self.opcode & 0x0F00;
-        let val = self.opcode & 0x00FF;
 
-        if self.register[x as usize] != val as u8 {
+    // 4XNN: Skip next instruction if VX != NN
+    fn skip_not_equal(&mut self, x: usize, nn: u8) {
+        if self.register[x] != nn {
             self.program_counter += 2;
         }
 
@@ -104,11 +266,8 @@ impl Cpu {
     }
 
     // 5XY0: Skip next instruction if VX == VY
-    fn skip_regs_equal(&mut self) {
-        let x = self.opcode & 0x0F00;
-        let y = self.opcode & 0x00F0;
-
-        if self.register[x as usize] == self.register[y as usize] {
+    fn skip_regs_equal(&mut self, x: usize, y: usize) {
+        if self.register[x] == self.register[y] {
             self.program_counter += 2;
         }
 
@@ -116,32 +275,34 @@ impl Cpu {
     }
 
     // 6XNN: Set VX = NN
-    fn set_vx_num(&mut self) {
-        let x = self.opcode & 0x0F00;
-        let val = self.opcode & 0x00FF;
-
-        self.register[x as usize] = val as u8;
+    fn set_vx_num(&mut self, x: usize, nn: u8) {
+        self.register[x] = nn;
 
         self.program_counter += 2;
     }
 
     // 7XNN: Add NN to VX
-    fn add_vx_num(&mut self) {
-        let x = self.opcode & 0x0F00;
-        let val = self.opcode & 0x00FF;
-
-        self.register[x as usize] += val as u8;
+    fn add_vx_num(&mut self, x: usize, nn: u8) {
+        self.register[x] = self.register[x].wrapping_add(nn);
 
         self.program_counter += 2;
     }
-    
+
     // Opcode 0x8000 instructions
-    fn opcode_8(&mut self) {
-        let x = self.opcode & 0x0F00;
-        let y = self.opcode & 0x00F0;
+    fn opcode_8(&mut self, x: usize, y: usize, n: u8) {
+        match n {
+            0x0 => self.set_vx_vy(x, y),
+            0x1 => self.set_vx_or(x, y),
+            0x2 => self.set_vx_and(x, y),
+            0x3 => self.set_vx_xor(x, y),
+            0x4 => self.add_vx_vy(x, y),
+            0x5 => self.sub_vx_vy(x, y),
+            0x6 => self.shr_vx(x, y),
+            0x7 => self.sub_vy_vx(x, y),
+            0xE => self.shl_vx(x, y),
+            _ => println!("Unknown opcode: 8XY{:X}", n),
+        }
 
-        // use lookup table to call the appropriate instruction passing x and y as usize
-    
         self.program_counter += 2;
     }
 
@@ -152,73 +313,78 @@ impl Cpu {
 
     // 8XY1: Set VX = VX | VY
     fn set_vx_or(&mut self, x: usize, y: usize) {
-        self.register[x] = self.register[x] | self.register[y];
+        self.register[x] |= self.register[y];
+
+        if self.quirks.reset_vf_on_logic {
+            self.register[0xF] = 0;
+        }
     }
 
     // 8XY2: Set VX = VX & VY
     fn set_vx_and(&mut self, x: usize, y: usize) {
-        self.register[x] = self.register[x] & self.register[y];
+        self.register[x] &= self.register[y];
+
+        if self.quirks.reset_vf_on_logic {
+            self.register[0xF] = 0;
+        }
     }
 
     // 8XY3: Set VX = VX ^ VY
     fn set_vx_xor(&mut self, x: usize, y: usize) {
-        self.register[x] = self.register[x] & self.register[y];
+        self.register[x] ^= self.register[y];
+
+        if self.quirks.reset_vf_on_logic {
+            self.register[0xF] = 0;
+        }
     }
 
-    // 8XY4: VX += VY with carry flag
+    // 8XY4: VX += VY, VF = 1 on carry
     fn add_vx_vy(&mut self, x: usize, y: usize) {
-        self.register[x] += self.register[y];
+        let (result, carry) = self.register[x].overflowing_add(self.register[y]);
 
-        if(self.register[x] < self.register[y]) {
-            self.register[0xF] = 1;
-        } 
-        else {
-            self.register[0xF] = 0;
-        }
+        self.register[x] = result;
+        self.register[0xF] = carry as u8;
     }
 
-    // 8XY5: VX -= VY with borrow flag
-    fn sub_vx_vy(&mut self, x: usize, y: usize) { 
-        if(self.register[x] < self.register[y]) {
-            self.register[0xF] = 0;
-        }
-        else {
-            self.register[0xF] = 1;
-        }
-        
-        self.register[x] -= self.register[y];
+    // 8XY5: VX -= VY, VF = 1 when there is no borrow
+    fn sub_vx_vy(&mut self, x: usize, y: usize) {
+        let (result, borrow) = self.register[x].overflowing_sub(self.register[y]);
+
+        self.register[x] = result;
+        self.register[0xF] = !borrow as u8;
     }
 
     // 8XY6: Set VF to LSB of VX then shift VX right
     fn shr_vx(&mut self, x: usize, y: usize) {
+        if self.quirks.shift_uses_vy {
+            self.register[x] = self.register[y];
+        }
+
         self.register[0xF] = self.register[x] & 1;
-        self.register[x] = self.register[x] >> 1;
+        self.register[x] >>= 1;
     }
 
-    // 8XY7: Set VX = VY - VX and set borrow flag
+    // 8XY7: Set VX = VY - VX, VF = 1 when there is no borrow
     fn sub_vy_vx(&mut self, x: usize, y: usize) {
-        if(self.register[y] < self.register[x]) {
-            self.register[0xF] = 0;
-        }
-        else {
-            self.register[0xF] = 1;
-        }
+        let (result, borrow) = self.register[y].overflowing_sub(self.register[x]);
 
-        self.register[x] = self.register[y] - self.register[x];
+        self.register[x] = result;
+        self.register[0xF] = !borrow as u8;
     }
 
     // 8XYE: Set flag to MSB then shift VX left
     fn shl_vx(&mut self, x: usize, y: usize) {
-        self.register[0xF] = self.register[x] & 0x80;
-        self.register[x] = self.register[x] << 1;
+        if self.quirks.shift_uses_vy {
+            self.register[x] = self.register[y];
+        }
+
+        self.register[0xF] = (self.register[x] & 0x80) >> 7;
+        self.register[x] <<= 1;
     }
 
     // 9XY0: Skip the instruction if VX != VY
-    fn skip_regs_not_equal(&mut self) {
-        let x = self.opcode & 0x0F00;
-        let y = self.opcode & 0x00F0;
-
-        if(self.register[x as usize] != self.register[y as usize]) {
+    fn skip_regs_not_equal(&mut self, x: usize, y: usize) {
+        if self.register[x] != self.register[y] {
             self.program_counter += 2;
         }
 
@@ -226,33 +392,99 @@ impl Cpu {
     }
 
     // ANNN: Set address register
-    fn set_adr_reg(&mut self) {
-        self.address_register = self.opcode & 0x0FFF;
+    fn set_adr_reg(&mut self, nnn: u16) {
+        self.address_register = nnn;
 
         self.program_counter += 2;
     }
 
-    // BNNN: Jump to address NNN+V0
-    fn jump_add(&mut self) {
-        self.program_counter = (self.opcode & 0x0FFF) + self.register[0] as u16;
+    // BNNN: Jump to address NNN+V0 (or NNN+VX in the bnnn_uses_vx quirk, where
+    // X is the top nibble of NNN)
+    fn jump_add(&mut self, x: usize, nnn: u16) {
+        let base = if self.quirks.bnnn_uses_vx {
+            self.register[x] as u16
+        } else {
+            self.register[0] as u16
+        };
+
+        self.program_counter = nnn + base;
     }
 
-    // CXNN
-    
-    // DXYN
+    // CXNN: Set VX = rand() & NN
+    fn rand_vx(&mut self, x: usize, nn: u8) {
+        let random_byte: u8 = self.rng.gen();
+
+        self.register[x] = random_byte & nn;
+
+        self.program_counter += 2;
+    }
+
+    // DXYN: Draw an n-byte sprite from memory[I] at (VX, VY), XORing it into the
+    // graphics buffer and setting VF on collision.
+    fn draw_sprite(&mut self, x: usize, y: usize, n: u8) {
+        self.register[0xF] = 0;
 
-    // 0xEX9E and EXA1 skip instruction of key in VX if it is pressed/not pressed depending on
+        for row in 0..(n as usize) {
+            let sprite_byte = self.memory[(self.address_register as usize + row) % 4096];
+
+            for col in 0..8 {
+                if sprite_byte & (0x80 >> col) == 0 {
+                    continue;
+                }
+
+                let px = (self.register[x] as usize + col) % 64;
+                let py = (self.register[y] as usize + row) % 32;
+                let index = py * 64 + px;
+
+                if self.graphics[index] == 1 {
+                    self.register[0xF] = 1;
+                }
+
+                self.graphics[index] ^= 1;
+            }
+        }
+
+        self.draw_flag = true;
+
+        self.program_counter += 2;
+    }
+
+    // 0xEX9E and EXA1 skip instruction if key in VX is pressed/not pressed depending on
     // opcode
-    fn skip_key_press(&mut self) {
-        // TODO: Implement key press data first
+    fn skip_key_press(&mut self, x: usize, on_press: bool) {
+        let pressed = self.key[(self.register[x] & 0xF) as usize] != 0;
+
+        if pressed == on_press {
+            self.program_counter += 2;
+        }
+
+        self.program_counter += 2;
     }
 
     // Opcode 0xF000 instructions
-    fn opcode_f(&mut self) {
-        let x = self.opcode & 0x0F00;
+    fn opcode_f(&mut self, x: usize, nn: u8) {
+        // FX0A blocks on no key being pressed, so it controls its own
+        // program_counter advance instead of sharing the common one below.
+        if nn == 0x0A {
+            if self.set_vx_key(x) {
+                self.program_counter += 2;
+            }
+
+            return;
+        }
+
+        match nn {
+            0x07 => self.set_vx_delay(x),
+            0x15 => self.set_delay_vx(x),
+            0x18 => self.set_sound_vx(x),
+            0x1E => self.add_adr_reg(x),
+            0x29 => self.set_i_font(x),
+            0x33 => self.store_bcd(x),
+            0x55 => self.store_regs(x),
+            0x65 => self.load_regs(x),
+            _ => println!("Unknown opcode: FX{:02X}", nn),
+        }
 
-        // Call function from lookup table
-        
         self.program_counter += 2;
     }
 
@@ -261,9 +493,17 @@ impl Cpu {
         self.register[x] = self.delay_timer;
     }
 
-    // FX0A: Store key press in VX
-    fn set_vx_key(&mut self, x: usize) {
-        // TODO: Key press
+    // FX0A: Store key press in VX, blocking (by not advancing program_counter)
+    // until some key is down. Returns whether a key was found.
+    fn set_vx_key(&mut self, x: usize) -> bool {
+        for i in 0..self.key.len() {
+            if self.key[i] != 0 {
+                self.register[x] = i as u8;
+                return true;
+            }
+        }
+
+        false
     }
 
     // FX15: Set delay_timer = VX
@@ -281,13 +521,41 @@ impl Cpu {
         self.address_register += self.register[x] as u16;
     }
 
-    // FX29
-    
-    // FX33
-    
-    // FX55
-    
-    // FX65
+    // FX29: Set I to the address of the fontset sprite for the hex digit in VX
+    fn set_i_font(&mut self, x: usize) {
+        self.address_register = self.register[x] as u16 * 5;
+    }
+
+    // FX33: Store the binary-coded decimal of VX at I, I+1, I+2
+    fn store_bcd(&mut self, x: usize) {
+        let val = self.register[x];
+
+        self.memory[self.address_register as usize % 4096] = val / 100;
+        self.memory[(self.address_register as usize + 1) % 4096] = (val / 10) % 10;
+        self.memory[(self.address_register as usize + 2) % 4096] = val % 10;
+    }
+
+    // FX55: Dump registers V0..=VX into memory starting at I
+    fn store_regs(&mut self, x: usize) {
+        for i in 0..=x {
+            self.memory[(self.address_register as usize + i) % 4096] = self.register[i];
+        }
+
+        if self.quirks.fx55_increments_i {
+            self.address_register += x as u16 + 1;
+        }
+    }
+
+    // FX65: Load registers V0..=VX from memory starting at I
+    fn load_regs(&mut self, x: usize) {
+        for i in 0..=x {
+            self.register[i] = self.memory[(self.address_register as usize + i) % 4096];
+        }
+
+        if self.quirks.fx55_increments_i {
+            self.address_register += x as u16 + 1;
+        }
+    }
 }
 
 // Each character in the font set is 5 characters hide and 4 pixels wide
@@ -315,3 +583,163 @@ static FONTSET: [u8; 80] = [
 //    nullop, nullop, nullop, nullop, nullop, nullop, nullop, nullop, nullop, nullop, nullop, nullop, nullop, nullop,
 //    nullop, nullop,
 //];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_vx_vy_sets_carry_on_overflow() {
+        let mut cpu = Cpu::new();
+        cpu.register[0] = 0xFF;
+        cpu.register[1] = 2;
+
+        cpu.add_vx_vy(0, 1);
+
+        assert_eq!(cpu.register[0], 1);
+        assert_eq!(cpu.register[0xF], 1);
+    }
+
+    #[test]
+    fn add_vx_vy_clears_carry_without_overflow() {
+        let mut cpu = Cpu::new();
+        cpu.register[0] = 1;
+        cpu.register[1] = 2;
+
+        cpu.add_vx_vy(0, 1);
+
+        assert_eq!(cpu.register[0], 3);
+        assert_eq!(cpu.register[0xF], 0);
+    }
+
+    #[test]
+    fn sub_vx_vy_sets_vf_on_no_borrow() {
+        let mut cpu = Cpu::new();
+        cpu.register[0] = 5;
+        cpu.register[1] = 2;
+
+        cpu.sub_vx_vy(0, 1);
+
+        assert_eq!(cpu.register[0], 3);
+        assert_eq!(cpu.register[0xF], 1);
+    }
+
+    #[test]
+    fn sub_vx_vy_clears_vf_on_borrow() {
+        let mut cpu = Cpu::new();
+        cpu.register[0] = 2;
+        cpu.register[1] = 5;
+
+        cpu.sub_vx_vy(0, 1);
+
+        assert_eq!(cpu.register[0], (2u8).wrapping_sub(5));
+        assert_eq!(cpu.register[0xF], 0);
+    }
+
+    #[test]
+    fn sub_vy_vx_sets_vf_on_no_borrow() {
+        let mut cpu = Cpu::new();
+        cpu.register[0] = 2;
+        cpu.register[1] = 5;
+
+        cpu.sub_vy_vx(0, 1);
+
+        assert_eq!(cpu.register[0], 3);
+        assert_eq!(cpu.register[0xF], 1);
+    }
+
+    #[test]
+    fn sub_vy_vx_clears_vf_on_borrow() {
+        let mut cpu = Cpu::new();
+        cpu.register[0] = 5;
+        cpu.register[1] = 2;
+
+        cpu.sub_vy_vx(0, 1);
+
+        assert_eq!(cpu.register[0], (2u8).wrapping_sub(5));
+        assert_eq!(cpu.register[0xF], 0);
+    }
+
+    #[test]
+    fn set_vx_xor_xors_registers() {
+        let mut cpu = Cpu::new();
+        cpu.register[0] = 0b1010;
+        cpu.register[1] = 0b0110;
+
+        cpu.set_vx_xor(0, 1);
+
+        assert_eq!(cpu.register[0], 0b1100);
+    }
+
+    #[test]
+    fn shl_vx_sets_vf_to_msb() {
+        let mut cpu = Cpu::new();
+        cpu.register[1] = 0x81;
+
+        cpu.shl_vx(0, 1);
+
+        assert_eq!(cpu.register[0], 0x02);
+        assert_eq!(cpu.register[0xF], 1);
+    }
+
+    #[test]
+    fn draw_sprite_sets_vf_on_collision() {
+        let mut cpu = Cpu::new();
+        cpu.address_register = 0x300;
+        cpu.memory[0x300] = 0x80; // single lit pixel in the top-left corner
+
+        cpu.draw_sprite(0, 0, 1);
+        assert_eq!(cpu.register[0xF], 0);
+        assert_eq!(cpu.graphics[0], 1);
+
+        cpu.draw_sprite(0, 0, 1);
+        assert_eq!(cpu.register[0xF], 1);
+        assert_eq!(cpu.graphics[0], 0);
+    }
+
+    #[test]
+    fn store_bcd_splits_value_into_digits() {
+        let mut cpu = Cpu::new();
+        cpu.address_register = 0x300;
+        cpu.register[0] = 156;
+
+        cpu.store_bcd(0);
+
+        assert_eq!(cpu.memory[0x300], 1);
+        assert_eq!(cpu.memory[0x301], 5);
+        assert_eq!(cpu.memory[0x302], 6);
+    }
+
+    #[test]
+    fn store_regs_and_load_regs_round_trip() {
+        let mut cpu = Cpu::new();
+        cpu.address_register = 0x300;
+        cpu.register[0] = 1;
+        cpu.register[1] = 2;
+        cpu.register[2] = 3;
+
+        cpu.store_regs(2);
+        cpu.register = [0; 16];
+        cpu.address_register = 0x300;
+        cpu.load_regs(2);
+
+        assert_eq!(cpu.register[0], 1);
+        assert_eq!(cpu.register[1], 2);
+        assert_eq!(cpu.register[2], 3);
+    }
+
+    #[test]
+    fn opcode_f_0a_blocks_until_a_key_is_pressed() {
+        let mut cpu = Cpu::new();
+        let pc = cpu.program_counter;
+
+        cpu.opcode_f(0, 0x0A);
+        assert_eq!(cpu.program_counter, pc);
+
+        cpu.key[5] = 1;
+        cpu.opcode_f(0, 0x0A);
+
+        assert_eq!(cpu.register[0], 5);
+        assert_eq!(cpu.program_counter, pc + 2);
+    }
+}